@@ -0,0 +1,298 @@
+//! Abstraction over where block data and templates come from.
+//!
+//! `braidpool` previously hardwired a single `bitcoincore_rpc::Client`. The
+//! [`BlockSource`] trait lets `listener`/`fetcher` treat the JSON-RPC client
+//! and a read-only REST client interchangeably, and hold several of them so a
+//! transient outage on one bitcoind doesn't halt the pool. This mirrors the
+//! approach taken by `lightning-block-sync`, which fetches from a list of
+//! abstract sources rather than a single hardcoded client.
+
+use async_trait::async_trait;
+use bitcoin::block::Header as BlockHeader;
+use bitcoin::BlockHash;
+use bitcoincore_rpc::{jsonrpc, RpcApi};
+use bitcoincore_rpc_json::{GetBlockTemplateModes, GetBlockTemplateResult, GetBlockTemplateRules};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// HTTP timeout for the long-poll variant of `getblocktemplate`, which
+/// bitcoind can legitimately hold open for minutes waiting on a new block or
+/// a meaningful mempool change. This is the only timeout `long_poller` relies
+/// on: the call is always awaited to completion rather than raced against an
+/// external `tokio::time::timeout`, so it always returns (successfully or
+/// with a timeout error) instead of leaving a `spawn_blocking` thread running
+/// in the background past that point.
+const LONG_POLL_HTTP_TIMEOUT: Duration = Duration::from_secs(70);
+
+#[derive(Debug)]
+pub enum BlockSourceError {
+    Rpc(bitcoincore_rpc::Error),
+    Rest(reqwest::Error),
+    /// The operation isn't available on this source, e.g. `getblocktemplate`
+    /// isn't exposed over Bitcoin Core's REST interface.
+    Unsupported(&'static str),
+    Decode(String),
+}
+
+impl std::fmt::Display for BlockSourceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockSourceError::Rpc(err) => write!(f, "RPC error: {}", err),
+            BlockSourceError::Rest(err) => write!(f, "REST error: {}", err),
+            BlockSourceError::Unsupported(what) => write!(f, "unsupported on this source: {}", what),
+            BlockSourceError::Decode(err) => write!(f, "failed to decode REST response: {}", err),
+        }
+    }
+}
+
+/// A source of block chain data: either bitcoind's JSON-RPC interface or its
+/// read-only REST interface. Implementations are expected to be cheap to
+/// clone/share across tasks (the JSON-RPC client already is `Send + Sync`).
+#[async_trait]
+pub trait BlockSource: Send + Sync {
+    /// Human-readable name for logging, e.g. `"rpc:127.0.0.1:8332"`.
+    fn name(&self) -> &str;
+
+    /// `long_poll_id` carries forward the `longpollid` from a previous
+    /// template so bitcoind can hold the call open until a new block is
+    /// found or the mempool changes meaningfully, instead of returning
+    /// immediately.
+    async fn get_block_template(
+        &self,
+        rules: &[GetBlockTemplateRules],
+        long_poll_id: Option<String>,
+    ) -> Result<GetBlockTemplateResult, BlockSourceError>;
+
+    async fn get_best_block_hash(&self) -> Result<BlockHash, BlockSourceError>;
+
+    async fn get_block_header(&self, hash: &BlockHash) -> Result<BlockHeader, BlockSourceError>;
+
+    /// Validates a serialized candidate block via `getblocktemplate`'s
+    /// proposal mode without broadcasting it. `Ok(None)` means bitcoind
+    /// would accept the block; `Ok(Some(reason))` carries its rejection
+    /// reason.
+    async fn propose_block(&self, block_hex: String) -> Result<Option<String>, BlockSourceError>;
+
+    /// Submits a fully assembled, serialized block via `submitblock`.
+    /// `Ok(None)` means it was accepted; `Ok(Some(result))` carries
+    /// bitcoind's raw result string (e.g. `"duplicate"`, `"inconclusive"`,
+    /// or a rejection reason).
+    async fn submit_block(&self, block_hex: String) -> Result<Option<String>, BlockSourceError>;
+}
+
+/// `BlockSource` backed by bitcoind's JSON-RPC interface. Supports every
+/// operation, including `getblocktemplate`.
+pub struct JsonRpcBlockSource {
+    name: String,
+    rpc: Arc<bitcoincore_rpc::Client>,
+    /// A second client configured with [`LONG_POLL_HTTP_TIMEOUT`], used only
+    /// by the long-polling path of `get_block_template`. Kept separate from
+    /// `rpc` so every other call keeps the library's default (much shorter)
+    /// timeout.
+    long_poll_rpc: Arc<bitcoincore_rpc::Client>,
+}
+
+impl JsonRpcBlockSource {
+    pub fn new(
+        name: String,
+        bitcoin: &str,
+        rpc_port: u16,
+        rpc_user: String,
+        rpc_pass: String,
+    ) -> Result<Self, BlockSourceError> {
+        let rpc_url = format!("{}:{}", bitcoin, rpc_port);
+        let rpc = bitcoincore_rpc::Client::new(
+            &rpc_url,
+            bitcoincore_rpc::Auth::UserPass(rpc_user.clone(), rpc_pass.clone()),
+        )
+        .map_err(BlockSourceError::Rpc)?;
+        let long_poll_rpc =
+            Self::build_client(&rpc_url, &rpc_user, &rpc_pass, LONG_POLL_HTTP_TIMEOUT)?;
+
+        Ok(Self {
+            name,
+            rpc: Arc::new(rpc),
+            long_poll_rpc: Arc::new(long_poll_rpc),
+        })
+    }
+
+    fn build_client(
+        rpc_url: &str,
+        rpc_user: &str,
+        rpc_pass: &str,
+        timeout: Duration,
+    ) -> Result<bitcoincore_rpc::Client, BlockSourceError> {
+        let transport = jsonrpc::simple_http::Builder::new()
+            .url(rpc_url)
+            .map_err(|err| BlockSourceError::Decode(err.to_string()))?
+            .auth(rpc_user, Some(rpc_pass))
+            .timeout(timeout)
+            .build();
+        Ok(bitcoincore_rpc::Client::from_jsonrpc(
+            jsonrpc::Client::with_transport(transport),
+        ))
+    }
+}
+
+#[async_trait]
+impl BlockSource for JsonRpcBlockSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn get_block_template(
+        &self,
+        rules: &[GetBlockTemplateRules],
+        long_poll_id: Option<String>,
+    ) -> Result<GetBlockTemplateResult, BlockSourceError> {
+        let rpc = if long_poll_id.is_some() {
+            self.long_poll_rpc.clone()
+        } else {
+            self.rpc.clone()
+        };
+        let rules = rules.to_vec();
+        tokio::task::spawn_blocking(move || {
+            // `bitcoincore_rpc::RpcApi::get_block_template` has no way to
+            // pass `longpollid`, so build the `getblocktemplate` request
+            // object ourselves for the long-polling case.
+            match long_poll_id {
+                None => rpc.get_block_template(GetBlockTemplateModes::Template, &rules, &[]),
+                Some(long_poll_id) => rpc.call(
+                    "getblocktemplate",
+                    &[serde_json::json!({
+                        "mode": "template",
+                        "rules": rules,
+                        "longpollid": long_poll_id,
+                    })],
+                ),
+            }
+        })
+        .await
+        .expect("spawn_blocking getblocktemplate")
+        .map_err(BlockSourceError::Rpc)
+    }
+
+    async fn get_best_block_hash(&self) -> Result<BlockHash, BlockSourceError> {
+        let rpc = self.rpc.clone();
+        tokio::task::spawn_blocking(move || rpc.get_best_block_hash())
+            .await
+            .expect("spawn_blocking getbestblockhash")
+            .map_err(BlockSourceError::Rpc)
+    }
+
+    async fn get_block_header(&self, hash: &BlockHash) -> Result<BlockHeader, BlockSourceError> {
+        let rpc = self.rpc.clone();
+        let hash = *hash;
+        tokio::task::spawn_blocking(move || rpc.get_block_header(&hash))
+            .await
+            .expect("spawn_blocking getblockheader")
+            .map_err(BlockSourceError::Rpc)
+    }
+
+    async fn propose_block(&self, block_hex: String) -> Result<Option<String>, BlockSourceError> {
+        let rpc = self.rpc.clone();
+        tokio::task::spawn_blocking(move || {
+            rpc.call::<Option<String>>(
+                "getblocktemplate",
+                &[serde_json::json!({
+                    "mode": GetBlockTemplateModes::Proposal,
+                    "data": block_hex,
+                })],
+            )
+        })
+        .await
+        .expect("spawn_blocking getblocktemplate proposal")
+        .map_err(BlockSourceError::Rpc)
+    }
+
+    async fn submit_block(&self, block_hex: String) -> Result<Option<String>, BlockSourceError> {
+        let rpc = self.rpc.clone();
+        tokio::task::spawn_blocking(move || {
+            rpc.call::<Option<String>>("submitblock", &[serde_json::Value::String(block_hex)])
+        })
+        .await
+        .expect("spawn_blocking submitblock")
+        .map_err(BlockSourceError::Rpc)
+    }
+}
+
+/// `BlockSource` backed by bitcoind's read-only REST interface
+/// (`/rest/...`). `getblocktemplate` has no REST equivalent, so that method
+/// always returns [`BlockSourceError::Unsupported`]; this source only exists
+/// to let `fetcher` keep tracking chain tip/headers from a node that, say,
+/// doesn't have RPC credentials configured for us.
+pub struct RestBlockSource {
+    name: String,
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl RestBlockSource {
+    pub fn new(name: String, bitcoin: String, rest_port: u16) -> Self {
+        Self {
+            name,
+            base_url: format!("http://{}:{}/rest", bitcoin, rest_port),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl BlockSource for RestBlockSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn get_block_template(
+        &self,
+        _rules: &[GetBlockTemplateRules],
+        _long_poll_id: Option<String>,
+    ) -> Result<GetBlockTemplateResult, BlockSourceError> {
+        Err(BlockSourceError::Unsupported("getblocktemplate"))
+    }
+
+    async fn get_best_block_hash(&self) -> Result<BlockHash, BlockSourceError> {
+        let url = format!("{}/chaininfo.json", self.base_url);
+        let resp: serde_json::Value = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(BlockSourceError::Rest)?
+            .json()
+            .await
+            .map_err(BlockSourceError::Rest)?;
+
+        let best_block_hash = resp["bestblockhash"]
+            .as_str()
+            .ok_or_else(|| BlockSourceError::Decode("missing bestblockhash".into()))?;
+
+        BlockHash::from_str(best_block_hash)
+            .map_err(|e| BlockSourceError::Decode(e.to_string()))
+    }
+
+    async fn get_block_header(&self, hash: &BlockHash) -> Result<BlockHeader, BlockSourceError> {
+        let url = format!("{}/headers/1/{:x}.bin", self.base_url, hash);
+        let bytes = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .map_err(BlockSourceError::Rest)?
+            .bytes()
+            .await
+            .map_err(BlockSourceError::Rest)?;
+
+        bitcoin::consensus::deserialize(&bytes)
+            .map_err(|e| BlockSourceError::Decode(e.to_string()))
+    }
+
+    async fn propose_block(&self, _block_hex: String) -> Result<Option<String>, BlockSourceError> {
+        Err(BlockSourceError::Unsupported("getblocktemplate"))
+    }
+
+    async fn submit_block(&self, _block_hex: String) -> Result<Option<String>, BlockSourceError> {
+        Err(BlockSourceError::Unsupported("submitblock"))
+    }
+}
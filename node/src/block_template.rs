@@ -1,6 +1,9 @@
+use crate::block_source::BlockSource;
+use crate::block_template_summary::{summarize, BlockTemplateSummary, SummaryCache};
+use crate::chain_tracker::{classify_new_tip, ChainEvent, ChainTipWindow, TipUpdate};
 use async_zmq::StreamExt;
-use bitcoincore_rpc::RpcApi;
-use bitcoincore_rpc_json::{GetBlockTemplateModes, GetBlockTemplateResult, GetBlockTemplateRules};
+use bitcoincore_rpc_json::{GetBlockTemplateResult, GetBlockTemplateRules};
+use std::sync::Arc;
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::time::{sleep, Duration};
 
@@ -12,14 +15,40 @@ const BLOCK_TEMPLATE_RULES: [GetBlockTemplateRules; 4] = [
 ];
 
 const BACKOFF_BASE: u64 = 2;
-const MAX_RPC_FAILURES: u32 = 20;
+// Caps the exponent fed to `BACKOFF_BASE`, not the number of retries:
+// `fetcher` never gives up, it just stops backing off further after this
+// many full rotations through every configured `BlockSource`.
+const MAX_BACKOFF_ROTATIONS: u32 = 20;
 
 #[derive(Debug)]
 pub enum BlockTemplateError {
-    Rpc(bitcoincore_rpc::Error),
     Zmq(async_zmq::zmq::Error),
 }
 
+/// Logs a `getblocktemplate` failure, naming the source that's about to be
+/// tried next only when there's actually more than one configured — with a
+/// single source, `next_source` is always the same one `source` already is,
+/// and "failing over to itself" would be a misleading thing to print.
+fn log_source_failure(
+    context: &str,
+    source: &dyn BlockSource,
+    next_source: &dyn BlockSource,
+    sources_len: usize,
+    err: impl std::fmt::Display,
+) {
+    if sources_len == 1 {
+        log::error!("Error on {} via [{}]: {}. Retrying.", context, source.name(), err);
+    } else {
+        log::error!(
+            "Error on {} via [{}]: {}. Failing over to [{}].",
+            context,
+            source.name(),
+            err,
+            next_source.name()
+        );
+    }
+}
+
 fn zmq_setup(
     bitcoin: String,
     zmq_port: u16,
@@ -41,31 +70,46 @@ fn zmq_setup(
     Ok(zmq)
 }
 
-fn rpc_setup(
-    bitcoin: String,
-    rpc_port: u16,
-    rpc_user: String,
-    rpc_pass: String,
-) -> Result<bitcoincore_rpc::Client, BlockTemplateError> {
-    let rpc_url = format!("{}:{}", bitcoin, rpc_port);
-    match bitcoincore_rpc::Client::new(
-        &rpc_url,
-        bitcoincore_rpc::Auth::UserPass(rpc_user, rpc_pass),
-    ) {
-        Ok(client) => Ok(client),
-        Err(err) => Err(BlockTemplateError::Rpc(err)),
+/// Picks the source whose tip header has the highest `time`, which is the
+/// best cross-source proxy we have for "most caught up" (heights/hashes
+/// aren't directly comparable across sources that may sit on competing
+/// tips). Sources that can't be reached are skipped rather than failing the
+/// whole selection.
+async fn select_best_source(sources: &[Box<dyn BlockSource>]) -> usize {
+    let mut best_index = 0;
+    let mut best_time = 0u32;
+
+    for (index, source) in sources.iter().enumerate() {
+        let tip = match source.get_best_block_hash().await {
+            Ok(tip) => tip,
+            Err(err) => {
+                log::warn!("[{}] failed to query best block hash: {}", source.name(), err);
+                continue;
+            }
+        };
+        let header = match source.get_block_header(&tip).await {
+            Ok(header) => header,
+            Err(err) => {
+                log::warn!("[{}] failed to query block header: {}", source.name(), err);
+                continue;
+            }
+        };
+        if header.time >= best_time {
+            best_time = header.time;
+            best_index = index;
+        }
     }
+
+    best_index
 }
 
 pub async fn listener(
     bitcoin: String,
-    rpc_port: u16,
-    rpc_user: String,
-    rpc_pass: String,
     zmq_port: u16,
+    sources: Vec<Box<dyn BlockSource>>,
     block_template_tx: Sender<GetBlockTemplateResult>,
 ) -> Result<(), BlockTemplateError> {
-    let rpc: bitcoincore_rpc::Client = rpc_setup(bitcoin.clone(), rpc_port, rpc_user, rpc_pass)?;
+    assert!(!sources.is_empty(), "listener requires at least one BlockSource");
     let mut zmq: async_zmq::subscribe::Subscribe = zmq_setup(bitcoin.clone(), zmq_port)?;
 
     while let Some(msg) = zmq.next().await {
@@ -78,7 +122,7 @@ pub async fn listener(
                     "Received a new `hashblock` notification via ZeroMQ. \
                     Calling `getblocktemplate` RPC now..."
                 );
-                fetcher(&rpc, block_template_tx.clone()).await;
+                fetcher(&sources, block_template_tx.clone()).await;
             }
             Err(err) => return Err(BlockTemplateError::Zmq(err.into())),
         };
@@ -86,15 +130,23 @@ pub async fn listener(
     Ok(())
 }
 
+/// Fetches a fresh `getblocktemplate` from `sources`, starting with whichever
+/// reports the highest chain tip and rotating to the next configured source
+/// on failure instead of hard-halting. A hot spare bitcoind (or a read-only
+/// REST source used just for tip comparisons) keeps braidpool running
+/// through a transient outage on the primary.
 pub async fn fetcher(
-    rpc: &bitcoincore_rpc::Client,
+    sources: &[Box<dyn BlockSource>],
     block_template_tx: Sender<GetBlockTemplateResult>,
 ) {
-    let mut rpc_failure_counter = 0;
-    let mut rpc_failure_backoff;
+    assert!(!sources.is_empty(), "fetcher requires at least one BlockSource");
+
+    let mut source_index = select_best_source(sources).await;
+    let mut rotations_without_success = 0u32;
 
     loop {
-        match rpc.get_block_template(GetBlockTemplateModes::Template, &BLOCK_TEMPLATE_RULES, &[]) {
+        let source = &sources[source_index];
+        match source.get_block_template(&BLOCK_TEMPLATE_RULES, None).await {
             Ok(get_block_template_result) => {
                 block_template_tx
                     .send(get_block_template_result.clone())
@@ -103,42 +155,183 @@ pub async fn fetcher(
                 break;
             }
             Err(e) => {
-                rpc_failure_counter += 1;
-                if rpc_failure_counter > MAX_RPC_FAILURES {
+                let next_index = (source_index + 1) % sources.len();
+                log_source_failure(
+                    "`getblocktemplate`",
+                    source.as_ref(),
+                    sources[next_index].as_ref(),
+                    sources.len(),
+                    e,
+                );
+
+                // We've tried every configured source this lap with no luck;
+                // back off before starting the next lap.
+                if next_index == 0 {
+                    rotations_without_success += 1;
+                    let exponent = rotations_without_success.min(MAX_BACKOFF_ROTATIONS);
+                    let rpc_failure_backoff = u64::checked_pow(BACKOFF_BASE, exponent)
+                        .expect("MAX_BACKOFF_ROTATIONS doesn't allow overflow; qed");
                     log::error!(
-                        "Exceeded the maximum number of failed `getblocktemplate` RPC \
-                    attempts. Halting."
+                        "Exponential Backoff: all {} configured source(s) failed this lap, \
+                        waiting {} seconds before retrying `getblocktemplate`.",
+                        sources.len(),
+                        rpc_failure_backoff
                     );
-                    std::process::exit(1);
+                    sleep(Duration::from_secs(rpc_failure_backoff)).await;
                 }
-                rpc_failure_backoff = u64::checked_pow(BACKOFF_BASE, rpc_failure_counter.clone())
-                    .expect("MAX_RPC_FAILURES doesn't allow overflow; qed");
+                source_index = next_index;
+            }
+        }
+    }
+}
 
-                // sleep until it's time to try again
-                log::error!("Error on `getblocktemplate` RPC: {}", e);
-                log::error!(
-                    "Exponential Backoff: `getblocktemplate` RPC failed {} times, waiting {} \
-                    seconds before attempting `getblocktemplate` RPC again.",
-                    rpc_failure_counter,
-                    rpc_failure_backoff
+/// Long-polls `getblocktemplate` so templates refresh on mempool changes,
+/// not only on new blocks. Runs independently of `listener`'s ZMQ trigger;
+/// both send into the same `block_template_tx`. `consumer`'s tip-tracking
+/// classifies a second template for an already-seen tip as `Unchanged`, so
+/// no duplicate chain event is emitted for it; the template itself is still
+/// logged, summarized and forwarded.
+///
+/// Every `BlockSource::get_block_template` call here is awaited to
+/// completion rather than raced against an external timeout: bitcoind can
+/// legitimately hold a long-poll open for minutes, and racing a
+/// `spawn_blocking`-backed call with `tokio::time::timeout` doesn't actually
+/// cancel it — it just abandons the call (and its blocked OS thread)
+/// running in the background. `JsonRpcBlockSource` instead gives the
+/// long-poll call its own HTTP client with a matching timeout, so the call
+/// itself always returns (successfully or with a timeout error) instead of
+/// needing to be raced.
+pub async fn long_poller(
+    sources: Vec<Box<dyn BlockSource>>,
+    block_template_tx: Sender<GetBlockTemplateResult>,
+) {
+    assert!(!sources.is_empty(), "long_poller requires at least one BlockSource");
+
+    let mut source_index = select_best_source(&sources).await;
+    let mut long_poll_id: Option<String> = None;
+
+    loop {
+        let source = &sources[source_index];
+        match source.get_block_template(&BLOCK_TEMPLATE_RULES, long_poll_id.clone()).await {
+            Ok(get_block_template_result) => {
+                long_poll_id = get_block_template_result.long_poll_id.clone();
+                block_template_tx
+                    .send(get_block_template_result)
+                    .await
+                    .expect("send block template over mpsc channel");
+            }
+            Err(e) => {
+                let next_index = (source_index + 1) % sources.len();
+                log_source_failure(
+                    "long-polling `getblocktemplate`",
+                    source.as_ref(),
+                    sources[next_index].as_ref(),
+                    sources.len(),
+                    e,
                 );
-                sleep(Duration::from_secs(rpc_failure_backoff)).await;
+                source_index = next_index;
+                // The new source has its own `longpollid` namespace.
+                long_poll_id = None;
+                sleep(Duration::from_secs(BACKOFF_BASE)).await;
             }
         }
     }
 }
 
 // dummy placeholder function to consume the received block templates
-pub async fn consumer(mut block_template_rx: Receiver<GetBlockTemplateResult>) {
-    let mut last_block_template_height = 0;
+pub async fn consumer(
+    source: Arc<dyn BlockSource>,
+    mut block_template_rx: Receiver<GetBlockTemplateResult>,
+    chain_event_tx: Sender<ChainEvent>,
+    summary_tx: Sender<BlockTemplateSummary>,
+) {
+    let mut window = ChainTipWindow::new();
+    let mut summary_cache = SummaryCache::new();
+
     while let Some(block_template) = block_template_rx.recv().await {
-        // if block template is from some outdated exponential backoff RPC, ignore it
-        if block_template.height > last_block_template_height {
-            log::info!(
-                "Received new block template via `getblocktemplate` RPC: {:?}",
-                block_template
-            );
-            last_block_template_height = block_template.height;
+        let new_height = block_template.height;
+        let prev_hash = block_template.previous_block_hash;
+        let prev_height = new_height.saturating_sub(1);
+
+        match classify_new_tip(source.as_ref(), &mut window, prev_height, prev_hash).await {
+            TipUpdate::Stale => {
+                log::debug!(
+                    "Discarding stale `getblocktemplate` reply for height {}",
+                    new_height
+                );
+                continue;
+            }
+            TipUpdate::Unknown => {
+                log::error!(
+                    "Could not classify the reported tip for template at height {}; discarding.",
+                    new_height
+                );
+                continue;
+            }
+            TipUpdate::Reorg(event) => {
+                log::warn!("Detected chain reorg: {:?}", event);
+                chain_event_tx
+                    .send(event)
+                    .await
+                    .expect("send chain event over mpsc channel");
+            }
+            TipUpdate::Seeded | TipUpdate::Advanced | TipUpdate::Unchanged => {}
+        }
+
+        log::info!(
+            "Received new block template via `getblocktemplate` RPC: {:?}",
+            block_template
+        );
+
+        match summarize(source.as_ref(), &block_template, &mut summary_cache).await {
+            Ok(summary) => {
+                summary_tx
+                    .send(summary)
+                    .await
+                    .expect("send block template summary over mpsc channel");
+            }
+            Err(err) => {
+                log::error!(
+                    "[{}] failed to summarize block template at height {}: {}",
+                    source.name(),
+                    new_height,
+                    err
+                );
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_hash, test_header, FakeBlockSource};
+    use bitcoin::hashes::Hash;
+    use bitcoin::BlockHash;
+
+    fn reachable(name: &str, hash: BlockHash, time: u32) -> FakeBlockSource {
+        FakeBlockSource::named(name)
+            .with_best(hash)
+            .with_header(hash, test_header(BlockHash::all_zeros(), time))
+    }
+
+    #[tokio::test]
+    async fn select_best_source_prefers_the_most_recent_tip() {
+        let sources: Vec<Box<dyn BlockSource>> = vec![
+            Box::new(reachable("stale", test_hash(1), 100)),
+            Box::new(reachable("fresh", test_hash(2), 200)),
+        ];
+
+        assert_eq!(select_best_source(&sources).await, 1);
+    }
+
+    #[tokio::test]
+    async fn select_best_source_skips_unreachable_sources() {
+        let sources: Vec<Box<dyn BlockSource>> = vec![
+            Box::new(FakeBlockSource::named("down")),
+            Box::new(reachable("up", test_hash(1), 100)),
+        ];
+
+        assert_eq!(select_best_source(&sources).await, 1);
+    }
+}
@@ -0,0 +1,155 @@
+//! Structured, verbose metadata about each new block template and its
+//! just-connected tip, so downstream braid logic (share weighting) has a
+//! real data feed instead of a raw `GetBlockTemplateResult` log line.
+
+use crate::block_source::{BlockSource, BlockSourceError};
+use bitcoin::block::Header as BlockHeader;
+use bitcoin::BlockHash;
+use bitcoincore_rpc_json::GetBlockTemplateResult;
+
+/// How many recent tip headers to average `bits` over for the network
+/// difficulty estimate. `summarize` walks this many headers back one RPC
+/// call at a time on the hot per-template path, so it's kept to roughly a
+/// day of blocks, well under Bitcoin's 2016-block retarget window, trading
+/// some accuracy for not stalling `consumer`.
+pub const DIFFICULTY_WINDOW: usize = 144;
+
+#[derive(Debug, Clone)]
+pub struct BlockTemplateSummary {
+    pub height: u64,
+    /// Header timestamp of the just-connected tip block.
+    pub time: u32,
+    /// `bits` of the tip block, expressed as a difficulty relative to the
+    /// genesis difficulty-1 target.
+    pub difficulty: f64,
+    pub coinbase_value: u64,
+    pub transaction_count: usize,
+    pub witness_commitment: Option<String>,
+    /// Average difficulty over the last [`DIFFICULTY_WINDOW`] headers (or
+    /// fewer, near chain start or when an ancestor header can't be fetched),
+    /// for a network hash-rate estimate without a separate tool.
+    pub average_difficulty: f64,
+}
+
+fn bits_to_difficulty(bits: u32) -> f64 {
+    let exponent = (bits >> 24) as i32;
+    let mantissa = (bits & 0x00ff_ffff) as f64;
+    let target = mantissa * 2f64.powi(8 * (exponent - 3));
+
+    const MAX_EXPONENT: i32 = 0x1d;
+    const MAX_MANTISSA: f64 = 0x00ffff as f64;
+    let max_target = MAX_MANTISSA * 2f64.powi(8 * (MAX_EXPONENT - 3));
+
+    max_target / target
+}
+
+/// Averages difficulty over `tip_header` and up to `window - 1` of its
+/// ancestors. Takes the already-fetched `tip_header` rather than re-fetching
+/// it, since callers already have it on hand.
+async fn average_difficulty(source: &dyn BlockSource, tip_header: &BlockHeader, window: usize) -> f64 {
+    let mut total = bits_to_difficulty(tip_header.bits.to_consensus());
+    let mut count = 1usize;
+    let mut hash = tip_header.prev_blockhash;
+
+    while count < window && hash != BlockHash::all_zeros() {
+        let header = match source.get_block_header(&hash).await {
+            Ok(header) => header,
+            Err(err) => {
+                log::warn!(
+                    "[{}] stopped averaging difficulty after {} header(s): {}",
+                    source.name(),
+                    count,
+                    err
+                );
+                break;
+            }
+        };
+        total += bits_to_difficulty(header.bits.to_consensus());
+        count += 1;
+        hash = header.prev_blockhash;
+    }
+
+    total / count as f64
+}
+
+/// Per-tip metrics that only depend on the tip header, not on the template
+/// built on top of it.
+struct TipMetrics {
+    time: u32,
+    difficulty: f64,
+    average_difficulty: f64,
+}
+
+async fn tip_metrics(source: &dyn BlockSource, tip_hash: BlockHash) -> Result<TipMetrics, BlockSourceError> {
+    let tip_header = source.get_block_header(&tip_hash).await?;
+    Ok(TipMetrics {
+        time: tip_header.time,
+        difficulty: bits_to_difficulty(tip_header.bits.to_consensus()),
+        average_difficulty: average_difficulty(source, &tip_header, DIFFICULTY_WINDOW).await,
+    })
+}
+
+/// Caches the last summarized tip's [`TipMetrics`] across calls to
+/// `summarize`, so a run of templates sharing the same `previousblockhash`
+/// (e.g. long-poll refreshes triggered only by mempool changes) don't each
+/// pay for a fresh `DIFFICULTY_WINDOW`-deep header walk for an unchanged tip.
+#[derive(Default)]
+pub struct SummaryCache {
+    tip_hash: Option<BlockHash>,
+    tip_metrics: Option<TipMetrics>,
+}
+
+impl SummaryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// Builds a [`BlockTemplateSummary`] for `template`'s just-connected tip,
+/// fetching its header for the fields `getblocktemplate` doesn't carry.
+/// `cache` is reused across calls so an unchanged tip doesn't re-walk
+/// `DIFFICULTY_WINDOW` headers every time.
+pub async fn summarize(
+    source: &dyn BlockSource,
+    template: &GetBlockTemplateResult,
+    cache: &mut SummaryCache,
+) -> Result<BlockTemplateSummary, BlockSourceError> {
+    let tip_hash = template.previous_block_hash;
+
+    if cache.tip_hash != Some(tip_hash) || cache.tip_metrics.is_none() {
+        cache.tip_metrics = Some(tip_metrics(source, tip_hash).await?);
+        cache.tip_hash = Some(tip_hash);
+    }
+    let metrics = cache
+        .tip_metrics
+        .as_ref()
+        .expect("populated immediately above");
+
+    Ok(BlockTemplateSummary {
+        height: template.height,
+        time: metrics.time,
+        difficulty: metrics.difficulty,
+        coinbase_value: template.coinbase_value.to_sat(),
+        transaction_count: template.transactions.len(),
+        witness_commitment: template.default_witness_commitment.clone(),
+        average_difficulty: metrics.average_difficulty,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn difficulty_1_target_has_difficulty_1() {
+        assert_eq!(bits_to_difficulty(0x1d00ffff), 1.0);
+    }
+
+    #[test]
+    fn shrinking_the_target_256x_raises_difficulty_256x() {
+        // Same mantissa, one exponent byte smaller: target is 1/256th, so
+        // difficulty is 256x.
+        let difficulty = bits_to_difficulty(0x1c00ffff);
+        assert!((difficulty - 256.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,310 @@
+//! Tracks recently-seen chain tips so `consumer` can tell a genuine reorg
+//! apart from a stale, already-superseded `getblocktemplate` reply (e.g. one
+//! that finally arrives after an exponential-backoff retry on a dead
+//! connection).
+
+use crate::block_source::BlockSource;
+use bitcoin::BlockHash;
+use std::collections::VecDeque;
+
+/// How many recent (height, hash) pairs to remember. Reorgs deeper than
+/// this are vanishingly rare; if one happens we can't find a common
+/// ancestor and say so rather than guessing.
+const WINDOW_SIZE: usize = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainEvent {
+    Reorg {
+        /// Height of the common ancestor: the rollback point downstream
+        /// braid logic needs to reapply `disconnected_hashes`' shares onto.
+        from_height: u64,
+        /// Height of the new, now-live tip.
+        to_height: u64,
+        /// Hashes that were on the old chain above the common ancestor,
+        /// highest first, so downstream braid logic knows what to roll back.
+        disconnected_hashes: Vec<BlockHash>,
+    },
+}
+
+/// Ring buffer of the most recently confirmed `(height, block hash)` pairs.
+#[derive(Default)]
+pub struct ChainTipWindow {
+    tips: VecDeque<(u64, BlockHash)>,
+}
+
+impl ChainTipWindow {
+    pub fn new() -> Self {
+        Self {
+            tips: VecDeque::with_capacity(WINDOW_SIZE),
+        }
+    }
+
+    pub fn hash_at(&self, height: u64) -> Option<BlockHash> {
+        self.tips
+            .iter()
+            .find(|(h, _)| *h == height)
+            .map(|(_, hash)| *hash)
+    }
+
+    pub fn contains_hash(&self, hash: &BlockHash) -> bool {
+        self.tips.iter().any(|(_, h)| h == hash)
+    }
+
+    pub fn latest_height(&self) -> Option<u64> {
+        self.tips.back().map(|(h, _)| *h)
+    }
+
+    fn oldest_height(&self) -> Option<u64> {
+        self.tips.front().map(|(h, _)| *h)
+    }
+
+    /// Hashes currently stored above `height`, highest first.
+    fn hashes_above(&self, height: u64) -> Vec<BlockHash> {
+        self.tips
+            .iter()
+            .rev()
+            .filter(|(h, _)| *h > height)
+            .map(|(_, hash)| *hash)
+            .collect()
+    }
+
+    /// Records `(height, hash)` as the live tip at `height`, replacing
+    /// whatever was previously recorded there (e.g. a reorg's disconnected
+    /// hash) so each height is only ever stored once.
+    pub fn push(&mut self, height: u64, hash: BlockHash) {
+        self.tips.retain(|(h, _)| *h != height);
+        if self.tips.len() == WINDOW_SIZE {
+            self.tips.pop_front();
+        }
+        self.tips.push_back((height, hash));
+    }
+}
+
+/// Walks `hash`/`height` backward one header at a time via `source` until it
+/// matches a hash already in `window`, i.e. the common ancestor of the old
+/// and new chains. Returns that ancestor's height plus the now-disconnected
+/// hashes from the old chain, or `None` if the ancestor lies outside the
+/// tracked window or a header along the way can't be fetched.
+pub async fn find_common_ancestor(
+    source: &dyn BlockSource,
+    window: &ChainTipWindow,
+    mut hash: BlockHash,
+    mut height: u64,
+) -> Option<(u64, Vec<BlockHash>)> {
+    let oldest_height = window.oldest_height()?;
+
+    loop {
+        if window.hash_at(height) == Some(hash) {
+            return Some((height, window.hashes_above(height)));
+        }
+        if height <= oldest_height {
+            return None;
+        }
+        let header = source.get_block_header(&hash).await.ok()?;
+        hash = header.prev_blockhash;
+        height -= 1;
+    }
+}
+
+/// Outcome of classifying an incoming template's `(height - 1,
+/// previousblockhash)` tip against [`ChainTipWindow`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TipUpdate {
+    /// First tip ever seen; the window was seeded with it.
+    Seeded,
+    /// This is exactly our last known tip again (e.g. a mempool-only
+    /// long-poll refresh); nothing chain-wise changed.
+    Unchanged,
+    /// The reported tip builds on a hash we've already superseded: a stale
+    /// reply, not a reorg.
+    Stale,
+    /// The reported tip extends our last known tip by one block, as normal.
+    Advanced,
+    /// The reported tip doesn't extend our last known tip: a genuine reorg.
+    Reorg(ChainEvent),
+    /// The reported tip looked new but its header couldn't be fetched, or no
+    /// common ancestor was found inside the tracked window.
+    Unknown,
+}
+
+/// Classifies `(tip_height, tip_hash)` — a template's `previousblockhash`
+/// and its height — against `window`, updating `window` in place for any
+/// outcome that confirms a new tip (`Seeded`/`Advanced`/`Reorg`).
+///
+/// The window only ever records a tip once it's been confirmed this way, so
+/// a plain `window.hash_at(tip_height)` lookup can never succeed for a tip
+/// being reported for the first time — that's the whole point of a tip
+/// (nothing has recorded it yet). Instead, a newly-reported tip is verified
+/// by fetching its own header and checking that its `prev_blockhash` equals
+/// our last known tip, i.e. that it's a direct child.
+pub async fn classify_new_tip(
+    source: &dyn BlockSource,
+    window: &mut ChainTipWindow,
+    tip_height: u64,
+    tip_hash: BlockHash,
+) -> TipUpdate {
+    let Some(last_height) = window.latest_height() else {
+        window.push(tip_height, tip_hash);
+        return TipUpdate::Seeded;
+    };
+    let last_hash = window
+        .hash_at(last_height)
+        .expect("latest_height implies an entry exists at that height");
+
+    if tip_hash == last_hash {
+        return TipUpdate::Unchanged;
+    }
+    if window.contains_hash(&tip_hash) {
+        return TipUpdate::Stale;
+    }
+
+    let header = match source.get_block_header(&tip_hash).await {
+        Ok(header) => header,
+        Err(_) => return TipUpdate::Unknown,
+    };
+
+    if header.prev_blockhash == last_hash {
+        window.push(tip_height, tip_hash);
+        return TipUpdate::Advanced;
+    }
+
+    match find_common_ancestor(source, window, tip_hash, tip_height).await {
+        Some((ancestor_height, disconnected_hashes)) => {
+            let event = ChainEvent::Reorg {
+                from_height: ancestor_height,
+                to_height: tip_height,
+                disconnected_hashes,
+            };
+            window.push(tip_height, tip_hash);
+            TipUpdate::Reorg(event)
+        }
+        None => TipUpdate::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_hash, test_header, FakeBlockSource};
+
+    #[tokio::test]
+    async fn first_tip_seeds_the_window() {
+        let source = FakeBlockSource::default();
+        let mut window = ChainTipWindow::new();
+
+        let update = classify_new_tip(&source, &mut window, 100, test_hash(1)).await;
+
+        assert_eq!(update, TipUpdate::Seeded);
+        assert_eq!(window.hash_at(100), Some(test_hash(1)));
+    }
+
+    /// Regression test: a sequence of ordinary, sequential blocks must not
+    /// be reported as reorgs just because the window hasn't recorded a
+    /// height yet the first time it's reported.
+    #[tokio::test]
+    async fn sequential_blocks_do_not_trigger_false_reorgs() {
+        let h100 = test_hash(100);
+        let h101 = test_hash(101);
+        let h102 = test_hash(102);
+
+        let source = FakeBlockSource::default()
+            .with_header(h101, test_header(h100, 0))
+            .with_header(h102, test_header(h101, 0));
+        let mut window = ChainTipWindow::new();
+
+        assert_eq!(
+            classify_new_tip(&source, &mut window, 100, h100).await,
+            TipUpdate::Seeded
+        );
+        assert_eq!(
+            classify_new_tip(&source, &mut window, 101, h101).await,
+            TipUpdate::Advanced
+        );
+        assert_eq!(
+            classify_new_tip(&source, &mut window, 102, h102).await,
+            TipUpdate::Advanced
+        );
+    }
+
+    #[tokio::test]
+    async fn same_tip_reported_again_is_unchanged() {
+        let h100 = test_hash(100);
+        let source = FakeBlockSource::default();
+        let mut window = ChainTipWindow::new();
+
+        classify_new_tip(&source, &mut window, 100, h100).await;
+        let update = classify_new_tip(&source, &mut window, 100, h100).await;
+
+        assert_eq!(update, TipUpdate::Unchanged);
+    }
+
+    #[tokio::test]
+    async fn stale_backoff_reply_is_discarded_not_treated_as_reorg() {
+        let h100 = test_hash(100);
+        let h101 = test_hash(101);
+
+        let source = FakeBlockSource::default().with_header(h101, test_header(h100, 0));
+        let mut window = ChainTipWindow::new();
+
+        classify_new_tip(&source, &mut window, 100, h100).await;
+        classify_new_tip(&source, &mut window, 101, h101).await;
+
+        // An outdated exponential-backoff response citing the old tip again.
+        let update = classify_new_tip(&source, &mut window, 100, h100).await;
+
+        assert_eq!(update, TipUpdate::Stale);
+    }
+
+    #[tokio::test]
+    async fn genuine_reorg_is_detected_and_disconnects_the_old_tip() {
+        let h100 = test_hash(100);
+        let h101_old = test_hash(101);
+        let h101_new = test_hash(201);
+
+        let source = FakeBlockSource::default()
+            .with_header(h101_old, test_header(h100, 0))
+            .with_header(h101_new, test_header(h100, 0));
+        let mut window = ChainTipWindow::new();
+
+        classify_new_tip(&source, &mut window, 100, h100).await;
+        classify_new_tip(&source, &mut window, 101, h101_old).await;
+
+        let update = classify_new_tip(&source, &mut window, 101, h101_new).await;
+
+        assert_eq!(
+            update,
+            TipUpdate::Reorg(ChainEvent::Reorg {
+                from_height: 100,
+                to_height: 101,
+                disconnected_hashes: vec![h101_old],
+            })
+        );
+        assert_eq!(window.hash_at(101), Some(h101_new));
+    }
+
+    /// Regression test: once a reorg has replaced the live hash at a height,
+    /// `hash_at` must resolve to that live hash, not the disconnected one it
+    /// replaced — otherwise the very next ordinary block gets compared
+    /// against the stale hash and is misreported as a second reorg.
+    #[tokio::test]
+    async fn blocks_after_a_reorg_do_not_trigger_a_false_second_reorg() {
+        let h100 = test_hash(100);
+        let h101_old = test_hash(101);
+        let h101_new = test_hash(201);
+        let h102 = test_hash(202);
+
+        let source = FakeBlockSource::default()
+            .with_header(h101_old, test_header(h100, 0))
+            .with_header(h101_new, test_header(h100, 0))
+            .with_header(h102, test_header(h101_new, 0));
+        let mut window = ChainTipWindow::new();
+
+        classify_new_tip(&source, &mut window, 100, h100).await;
+        classify_new_tip(&source, &mut window, 101, h101_old).await;
+        classify_new_tip(&source, &mut window, 101, h101_new).await;
+
+        let update = classify_new_tip(&source, &mut window, 102, h102).await;
+
+        assert_eq!(update, TipUpdate::Advanced);
+    }
+}
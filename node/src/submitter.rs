@@ -0,0 +1,141 @@
+//! Pushes solved blocks back to the network: proposal-mode pre-validation
+//! via `getblocktemplate`, then `submitblock`. This closes the loop braidpool
+//! otherwise lacks: template -> local assembly -> validate -> submit.
+
+use crate::block_source::{BlockSource, BlockSourceError};
+use std::sync::Arc;
+use tokio::sync::mpsc::Receiver;
+
+/// Interpretation of the `submitblock` RPC's result string: empty on
+/// success, otherwise bitcoind's internal block-validation state/reason.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubmitBlockResult {
+    /// bitcoind returned no result string: the block was accepted.
+    Accepted,
+    /// bitcoind already had this block. Treated as success, not an error.
+    Duplicate,
+    /// bitcoind had the block but didn't (re)validate it before returning.
+    DuplicateInconclusive,
+    /// The block wasn't on the most-work chain and wasn't fully validated.
+    Inconclusive,
+    /// bitcoind rejected the block outright, with its reason.
+    Rejected(String),
+}
+
+impl SubmitBlockResult {
+    fn from_rpc_result(result: Option<String>) -> Self {
+        match result.as_deref() {
+            None | Some("") => SubmitBlockResult::Accepted,
+            Some("duplicate") => SubmitBlockResult::Duplicate,
+            Some("duplicate-inconclusive") => SubmitBlockResult::DuplicateInconclusive,
+            Some("inconclusive") => SubmitBlockResult::Inconclusive,
+            Some(reason) => SubmitBlockResult::Rejected(reason.to_string()),
+        }
+    }
+
+    /// Whether bitcoind ended up with this block one way or another, as
+    /// opposed to an outright rejection.
+    pub fn is_success(&self) -> bool {
+        !matches!(self, SubmitBlockResult::Rejected(_))
+    }
+}
+
+/// Calls `submitblock` with a fully assembled, hex-encoded block.
+pub async fn submit_block(
+    source: &dyn BlockSource,
+    block_hex: String,
+) -> Result<SubmitBlockResult, BlockSourceError> {
+    let result = source.submit_block(block_hex).await?;
+    Ok(SubmitBlockResult::from_rpc_result(result))
+}
+
+/// Validates `block_hex` against bitcoind via `getblocktemplate` proposal
+/// mode without broadcasting it. `Ok(None)` means bitcoind would accept the
+/// block; `Ok(Some(reason))` carries its rejection reason.
+pub async fn propose_block(
+    source: &dyn BlockSource,
+    block_hex: String,
+) -> Result<Option<String>, BlockSourceError> {
+    source.propose_block(block_hex).await
+}
+
+/// Consumes braidpool-assembled blocks from `block_rx`, proposal-validates
+/// each one, and only calls `submitblock` if bitcoind would accept it.
+pub async fn submitter(source: Arc<dyn BlockSource>, mut block_rx: Receiver<String>) {
+    while let Some(block_hex) = block_rx.recv().await {
+        match propose_block(source.as_ref(), block_hex.clone()).await {
+            Ok(None) => {}
+            Ok(Some(reason)) => {
+                log::error!(
+                    "[{}] proposed block rejected by `getblocktemplate`: {}",
+                    source.name(),
+                    reason
+                );
+                continue;
+            }
+            Err(err) => {
+                log::error!(
+                    "[{}] failed to validate proposed block via `getblocktemplate`: {}",
+                    source.name(),
+                    err
+                );
+                continue;
+            }
+        }
+
+        match submit_block(source.as_ref(), block_hex).await {
+            Ok(SubmitBlockResult::Rejected(reason)) => {
+                log::error!("[{}] `submitblock` rejected block: {}", source.name(), reason);
+            }
+            Ok(result) => {
+                log::info!("[{}] submitted block: {:?}", source.name(), result);
+            }
+            Err(err) => {
+                log::error!("[{}] `submitblock` RPC failed: {}", source.name(), err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_result_string_means_accepted() {
+        assert_eq!(SubmitBlockResult::from_rpc_result(None), SubmitBlockResult::Accepted);
+    }
+
+    #[test]
+    fn known_result_strings_map_to_their_variant() {
+        assert_eq!(
+            SubmitBlockResult::from_rpc_result(Some("duplicate".to_string())),
+            SubmitBlockResult::Duplicate
+        );
+        assert_eq!(
+            SubmitBlockResult::from_rpc_result(Some("duplicate-inconclusive".to_string())),
+            SubmitBlockResult::DuplicateInconclusive
+        );
+        assert_eq!(
+            SubmitBlockResult::from_rpc_result(Some("inconclusive".to_string())),
+            SubmitBlockResult::Inconclusive
+        );
+    }
+
+    #[test]
+    fn unrecognized_result_string_is_a_rejection_with_reason() {
+        assert_eq!(
+            SubmitBlockResult::from_rpc_result(Some("bad-prevblk".to_string())),
+            SubmitBlockResult::Rejected("bad-prevblk".to_string())
+        );
+    }
+
+    #[test]
+    fn is_success_is_false_only_for_rejected() {
+        assert!(SubmitBlockResult::Accepted.is_success());
+        assert!(SubmitBlockResult::Duplicate.is_success());
+        assert!(SubmitBlockResult::DuplicateInconclusive.is_success());
+        assert!(SubmitBlockResult::Inconclusive.is_success());
+        assert!(!SubmitBlockResult::Rejected("bad-prevblk".to_string()).is_success());
+    }
+}
@@ -0,0 +1,94 @@
+//! Shared `BlockSource` test double, used by `chain_tracker` and
+//! `block_template`'s unit tests so each doesn't re-derive its own copy of
+//! the same fixture.
+#![cfg(test)]
+
+use crate::block_source::{BlockSource, BlockSourceError};
+use async_trait::async_trait;
+use bitcoin::block::{Header as BlockHeader, Version};
+use bitcoin::hashes::Hash;
+use bitcoin::{BlockHash, CompactTarget, TxMerkleNode};
+use bitcoincore_rpc_json::{GetBlockTemplateResult, GetBlockTemplateRules};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+pub fn test_hash(byte: u8) -> BlockHash {
+    BlockHash::from_byte_array([byte; 32])
+}
+
+pub fn test_header(prev_blockhash: BlockHash, time: u32) -> BlockHeader {
+    BlockHeader {
+        version: Version::ONE,
+        prev_blockhash,
+        merkle_root: TxMerkleNode::all_zeros(),
+        time,
+        bits: CompactTarget::from_consensus(0x1d00ffff),
+        nonce: 0,
+    }
+}
+
+/// An in-memory `BlockSource` backed by a hash -> header map, with an
+/// optional designated "best" tip. Looking up an unset header, or calling
+/// `get_best_block_hash` with no tip configured, fails the way an
+/// unreachable or pruned source would.
+#[derive(Default)]
+pub struct FakeBlockSource {
+    name: String,
+    headers: Mutex<HashMap<BlockHash, BlockHeader>>,
+    best: Option<BlockHash>,
+}
+
+impl FakeBlockSource {
+    pub fn named(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_header(self, hash: BlockHash, header: BlockHeader) -> Self {
+        self.headers.lock().unwrap().insert(hash, header);
+        self
+    }
+
+    pub fn with_best(mut self, hash: BlockHash) -> Self {
+        self.best = Some(hash);
+        self
+    }
+}
+
+#[async_trait]
+impl BlockSource for FakeBlockSource {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn get_block_template(
+        &self,
+        _rules: &[GetBlockTemplateRules],
+        _long_poll_id: Option<String>,
+    ) -> Result<GetBlockTemplateResult, BlockSourceError> {
+        unimplemented!("not exercised by these tests")
+    }
+
+    async fn get_best_block_hash(&self) -> Result<BlockHash, BlockSourceError> {
+        self.best.ok_or(BlockSourceError::Unsupported("unreachable"))
+    }
+
+    async fn get_block_header(&self, hash: &BlockHash) -> Result<BlockHeader, BlockSourceError> {
+        self.headers
+            .lock()
+            .unwrap()
+            .get(hash)
+            .copied()
+            .ok_or(BlockSourceError::Unsupported("unknown header"))
+    }
+
+    async fn propose_block(&self, _block_hex: String) -> Result<Option<String>, BlockSourceError> {
+        unimplemented!("not exercised by these tests")
+    }
+
+    async fn submit_block(&self, _block_hex: String) -> Result<Option<String>, BlockSourceError> {
+        unimplemented!("not exercised by these tests")
+    }
+}